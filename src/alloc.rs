@@ -0,0 +1,42 @@
+use std::alloc::Layout;
+
+/// Minimal allocator abstraction backing a [`crate::Symbol`] /
+/// [`crate::SymbolRegistry`]
+///
+/// This mirrors the (still unstable) standard library `Allocator`
+/// trait closely enough for our purposes while staying usable on
+/// stable Rust. Implement it to back a registry by e.g. an arena, a
+/// bump allocator, or shared/mmap'd memory.
+pub trait Allocator: Clone {
+	/// Allocate a block of memory matching `layout`
+	///
+	/// Returns a null pointer on failure.
+	fn alloc(&self, layout: Layout) -> *mut u8;
+
+	/// Deallocate a block of memory previously returned by [`Allocator::alloc`]
+	/// on this allocator with the same `layout`.
+	///
+	/// # Safety
+	///
+	/// `ptr` must have been returned by a call to
+	/// `self.alloc(layout)` (or an allocator it was cloned from) that
+	/// hasn't been deallocated yet.
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout);
+}
+
+/// The global heap allocator
+///
+/// Backed by `std::alloc::alloc` / `std::alloc::dealloc`; the default
+/// allocator for [`crate::Symbol`] / [`crate::SymbolRegistry`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Global;
+
+impl Allocator for Global {
+	fn alloc(&self, layout: Layout) -> *mut u8 {
+		unsafe { std::alloc::alloc(layout) }
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		std::alloc::dealloc(ptr, layout)
+	}
+}
@@ -0,0 +1,79 @@
+use crate::alloc::{Allocator, Global};
+use crate::Symbol;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+/// Wraps a [`Symbol`], comparing, hashing and ordering by identity
+/// (pointer) instead of by value
+///
+/// Symbols interned through the same registry guarantee "equal value
+/// implies same pointer", so once a batch of symbols came from one
+/// registry, comparing/hashing/ordering them by pointer is equivalent
+/// to doing so by value, but only needs to look at a single word
+/// instead of walking the whole string. Use this to get `HashMap` /
+/// `BTreeMap` keys that are cheap to hash and compare. This does not
+/// hold across [`crate::SymbolRegistry::retain`] /
+/// [`crate::SymbolRegistry::clear`]: a value inserted again after its
+/// symbol was untracked gets a new allocation, so the old and the new
+/// symbol compare unequal as `ById`.
+///
+/// The ordering this establishes is only a total, value-consistent
+/// order *within symbols from a single registry*: it depends on
+/// allocation addresses, which have no relation to the string values
+/// and aren't stable across registries or program runs. Standalone
+/// symbols (and symbols from different registries) still compare fine
+/// with `ById` (pointer equality / some arbitrary but consistent
+/// order), just not meaningfully by value. Because of that, `ById`
+/// deliberately does not implement `Borrow<str>`: looking one up by
+/// value would require a value-ordered/hashed collection, which is
+/// exactly what `ById` is not.
+#[derive(Debug, Clone)]
+pub struct ById<A: Allocator = Global>(pub Symbol<A>);
+
+impl<A: Allocator> ById<A> {
+	/// Wrap a symbol for identity-based comparison
+	pub fn new(symbol: Symbol<A>) -> Self {
+		ById(symbol)
+	}
+
+	/// Unwrap back into the underlying symbol
+	pub fn into_inner(self) -> Symbol<A> {
+		self.0
+	}
+}
+
+impl<A: Allocator> PartialEq for ById<A> {
+	fn eq(&self, other: &Self) -> bool {
+		self.0.ptr_eq(&other.0)
+	}
+}
+
+impl<A: Allocator> Eq for ById<A> {
+}
+
+impl<A: Allocator> Hash for ById<A> {
+	fn hash<H>(&self, state: &mut H)
+	where
+		H: Hasher,
+	{
+		self.0.addr().hash(state);
+	}
+}
+
+impl<A: Allocator> PartialOrd for ById<A> {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl<A: Allocator> Ord for ById<A> {
+	fn cmp(&self, other: &Self) -> Ordering {
+		self.0.addr().cmp(&other.0.addr())
+	}
+}
+
+impl<A: Allocator> From<Symbol<A>> for ById<A> {
+	fn from(symbol: Symbol<A>) -> Self {
+		ById(symbol)
+	}
+}
@@ -21,60 +21,93 @@
 //! (i.e. the reference count, the registry reference and the length of
 //! the string).
 
+mod alloc;
+mod by_id;
 mod symbol;
 mod symbol_no_rc;
 
-pub use self::symbol::Symbol;
+pub use self::alloc::{Allocator, Global};
+pub use self::by_id::ById;
+pub use self::symbol::{Symbol, WeakSymbol};
 use self::symbol_no_rc::SymbolNoRc;
 
 use std::collections::HashSet;
 use std::fmt;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
 
-#[derive(Debug)]
-struct Registry {
-	content: HashSet<SymbolNoRc>,
+struct Registry<A: Allocator> {
+	content: HashSet<SymbolNoRc<A>>,
 }
 
-impl Registry {
+impl<A: Allocator> Registry<A> {
 	fn new() -> Self {
 		Registry {
 			content: HashSet::new(),
 		}
 	}
 
-	fn find(&self, name: &str) -> Option<Symbol> {
+	fn find(&self, name: &str) -> Option<Symbol<A>> {
 		self.content.get(name).map(SymbolNoRc::symbol)
 	}
 }
 
+impl<A: Allocator> fmt::Debug for Registry<A> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("Registry").field("content", &self.content).finish()
+	}
+}
+
 /// Set of shared strings ("symbols")
 ///
 /// Unused symbols are removed from the set automatically.
+///
+/// Generic over the allocator `A` (defaulting to [`Global`]) used to
+/// back every symbol it interns; see [`SymbolRegistry::new_in`].
 #[derive(Clone)]
-pub struct SymbolRegistry {
-	registry: Arc<Mutex<Registry>>,
+pub struct SymbolRegistry<A: Allocator = Global> {
+	registry: Arc<Mutex<Registry<A>>>,
+	alloc: A,
 }
 
-impl SymbolRegistry {
+static GLOBAL_REGISTRY: OnceLock<SymbolRegistry> = OnceLock::new();
+
+impl SymbolRegistry<Global> {
 	/// Create new registry.
 	pub fn new() -> Self {
+		Self::new_in(Global)
+	}
+
+	/// Process-wide default registry
+	///
+	/// Lazily initialized on first use; backs [`Symbol::intern`] and
+	/// [`Symbol::intern_static`] so callers don't need to thread a
+	/// `SymbolRegistry` through every call site.
+	pub fn global() -> &'static SymbolRegistry {
+		GLOBAL_REGISTRY.get_or_init(SymbolRegistry::new)
+	}
+}
+
+impl<A: Allocator> SymbolRegistry<A> {
+	/// Create new registry, allocating every symbol it interns through
+	/// `alloc`.
+	pub fn new_in(alloc: A) -> Self {
 		SymbolRegistry {
 			registry: Arc::new(Mutex::new(Registry::new())),
+			alloc,
 		}
 	}
 
 	/// Insert a string into the registry if not already present
 	///
 	/// Returns the symbol representing the value.
-	pub fn insert(&self, value: &str) -> Symbol {
+	pub fn insert(&self, value: &str) -> Symbol<A> {
 		let mut inner = self.registry.lock().expect("registry lock");
 
 		if let Some(entry) = inner.content.get(value) {
 			return entry.symbol();
 		}
 
-		let symbol = Symbol::new(value);
+		let symbol = Symbol::new_in(value, self.alloc.clone());
 		inner.content.insert(symbol.clone_no_rc());
 		debug_assert!(inner.content.get(value).expect("just inserted").0.ptr_eq(&symbol));
 		// now set registry: we shouldn't drop any symbol within the
@@ -85,15 +118,44 @@ impl SymbolRegistry {
 		symbol
 	}
 
+	/// Insert a string into the registry as a *persistent* symbol
+	///
+	/// Persistent symbols are excluded from the automatic cleanup that
+	/// normally removes a symbol from its registry (and frees it) once
+	/// its last `Symbol` handle is dropped: they are never deallocated,
+	/// and only leave the registry through an explicit
+	/// [`SymbolRegistry::retain`] / [`SymbolRegistry::clear`]. Use this for long-lived identifiers
+	/// (keywords, the empty string, ...) that should be interned once
+	/// and then compared by pointer for the rest of the program, without
+	/// risking the allocation disappearing. Dropping a persistent
+	/// `Symbol` doesn't touch its reference count; cloning it still
+	/// increments the count like for any other symbol.
+	///
+	/// If `value` is already present (persistent or not), the existing
+	/// symbol is returned unchanged.
+	pub fn insert_persistent(&self, value: &str) -> Symbol<A> {
+		let mut inner = self.registry.lock().expect("registry lock");
+
+		if let Some(entry) = inner.content.get(value) {
+			return entry.symbol();
+		}
+
+		let symbol = Symbol::new_persistent_in(value, self.alloc.clone());
+		inner.content.insert(symbol.clone_no_rc());
+		debug_assert!(inner.content.get(value).expect("just inserted").0.ptr_eq(&symbol));
+		unsafe { symbol.set_registry(Arc::downgrade(&self.registry)); }
+		symbol
+	}
+
 	/// Find symbol with value if stored in registry
-	pub fn find(&self, value: &str) -> Option<Symbol> {
+	pub fn find(&self, value: &str) -> Option<Symbol<A>> {
 		self.registry.lock().expect("registry lock").find(value)
 	}
 
 	/// Check whether symbol is in registry
 	///
 	/// The actual symbol (not its value) is checked.
-	pub fn is_local_symbol(&self, symbol: &Symbol) -> bool {
+	pub fn is_local_symbol(&self, symbol: &Symbol<A>) -> bool {
 		if let Some(symreg) = symbol.registry() {
 			symreg == *self
 		} else {
@@ -107,31 +169,96 @@ impl SymbolRegistry {
 	/// clone of it.
 	///
 	/// Otherwise it will search for the symbol by value.
-	pub fn find_symbol(&self, symbol: &Symbol) -> Option<Symbol> {
+	pub fn find_symbol(&self, symbol: &Symbol<A>) -> Option<Symbol<A>> {
 		if self.is_local_symbol(symbol) {
 			return Some(symbol.clone());
 		}
 
 		self.find(&**symbol)
 	}
+
+	/// Number of distinct symbols currently interned in this registry
+	pub fn len(&self) -> usize {
+		self.registry.lock().expect("registry lock").content.len()
+	}
+
+	/// Whether this registry currently holds no symbols
+	pub fn is_empty(&self) -> bool {
+		self.registry.lock().expect("registry lock").content.is_empty()
+	}
+
+	/// Call `f` for every symbol currently interned in this registry
+	///
+	/// Takes a snapshot (see [`SymbolRegistry::snapshot`]) and calls
+	/// `f` on it after releasing the registry lock, so `f` is free to
+	/// call back into this registry (e.g. `insert`, `find`, ...)
+	/// without deadlocking.
+	pub fn for_each<F: FnMut(&Symbol<A>)>(&self, mut f: F) {
+		for symbol in self.snapshot() {
+			f(&symbol);
+		}
+	}
+
+	/// Collect a snapshot of every symbol currently interned in this
+	/// registry
+	///
+	/// Cost (both time and the size of the returned `Vec`) is
+	/// proportional to the number of currently live symbols.
+	pub fn snapshot(&self) -> Vec<Symbol<A>> {
+		let inner = self.registry.lock().expect("registry lock");
+		inner.content.iter().map(SymbolNoRc::symbol).collect()
+	}
+
+	/// Drop this registry's tracking of every currently interned symbol
+	/// for which `predicate` returns `false`
+	///
+	/// This only removes the registry's own (weak) bookkeeping: any
+	/// `Symbol` handle still held elsewhere keeps working, it just no
+	/// longer gets deduplicated against, nor found again via `find`.
+	///
+	/// `predicate` is evaluated against a snapshot (see
+	/// [`SymbolRegistry::snapshot`]) after releasing the registry
+	/// lock, so it may call back into this registry. A symbol that got
+	/// re-inserted (by a concurrent `insert`) in between is left alone
+	/// even if it was the one `predicate` rejected.
+	pub fn retain<F: FnMut(&Symbol<A>) -> bool>(&self, mut predicate: F) {
+		let to_remove: Vec<Symbol<A>> = self.snapshot().into_iter().filter(|symbol| !predicate(symbol)).collect();
+		if to_remove.is_empty() {
+			return;
+		}
+
+		let mut inner = self.registry.lock().expect("registry lock");
+		for symbol in &to_remove {
+			if inner.content.get(&**symbol).is_some_and(|have| have.0.ptr_eq(symbol)) {
+				inner.content.remove(&**symbol);
+			}
+		}
+	}
+
+	/// Drop this registry's tracking of every currently interned symbol
+	///
+	/// See [`SymbolRegistry::retain`].
+	pub fn clear(&self) {
+		self.registry.lock().expect("registry lock").content.clear();
+	}
 }
 
-impl Default for SymbolRegistry {
+impl Default for SymbolRegistry<Global> {
 	fn default() -> Self {
 		SymbolRegistry::new()
 	}
 }
 
-impl PartialEq for SymbolRegistry {
-	fn eq(&self, other: &SymbolRegistry) -> bool {
+impl<A: Allocator> PartialEq for SymbolRegistry<A> {
+	fn eq(&self, other: &SymbolRegistry<A>) -> bool {
 		Arc::ptr_eq(&self.registry, &other.registry)
 	}
 }
 
-impl Eq for SymbolRegistry {
+impl<A: Allocator> Eq for SymbolRegistry<A> {
 }
 
-impl fmt::Debug for SymbolRegistry {
+impl<A: Allocator> fmt::Debug for SymbolRegistry<A> {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		f.debug_tuple("SymbolRegistry").field(&self.registry.lock().expect("registry lock").content).finish()
 	}
@@ -139,7 +266,7 @@ impl fmt::Debug for SymbolRegistry {
 
 #[cfg(test)]
 mod tests {
-	use crate::{Symbol, SymbolRegistry};
+	use crate::{Allocator, ById, Symbol, SymbolRegistry, WeakSymbol};
 
 	#[test]
 	fn standalone() {
@@ -170,4 +297,167 @@ mod tests {
 		assert!(r.is_local_symbol(&r.find_symbol(&s1).unwrap()));
 		assert_ne!(s1, s2);
 	}
+
+	#[test]
+	fn global() {
+		let s1 = Symbol::intern("global example");
+		let s2 = Symbol::intern("global example");
+		assert!(s1.ptr_eq(&s2));
+		assert!(SymbolRegistry::global().is_local_symbol(&s1));
+	}
+
+	#[test]
+	fn weak() {
+		let s1 = Symbol::from("weak example");
+		let w: WeakSymbol = s1.downgrade();
+		let s2 = w.upgrade().unwrap();
+		assert!(s1.ptr_eq(&s2));
+		drop(s1);
+		drop(s2);
+		assert!(w.upgrade().is_none());
+	}
+
+	#[test]
+	fn by_id() {
+		use std::collections::HashSet;
+
+		let r = SymbolRegistry::new();
+		let s1 = r.insert("foo");
+		let s2 = r.insert("foo");
+		let s3 = r.insert("bar");
+		assert_eq!(s1.by_id(), s2.by_id());
+		assert_ne!(s1.by_id(), s3.by_id());
+
+		let mut set = HashSet::new();
+		set.insert(s1.by_id());
+		assert!(set.contains(&s2.by_id()));
+		assert!(!set.contains(&s3.by_id()));
+
+		let standalone1 = ById::from(Symbol::from("standalone"));
+		let standalone2 = ById::from(Symbol::from("standalone"));
+		assert_ne!(standalone1, standalone2);
+	}
+
+	#[derive(Clone)]
+	struct CountingAlloc(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+	impl Allocator for CountingAlloc {
+		fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+			self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+			unsafe { std::alloc::alloc(layout) }
+		}
+
+		unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+			self.0.fetch_sub(1, std::sync::atomic::Ordering::SeqCst);
+			std::alloc::dealloc(ptr, layout)
+		}
+	}
+
+	#[test]
+	fn custom_allocator() {
+		let live = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+		let alloc = CountingAlloc(live.clone());
+
+		let r = SymbolRegistry::new_in(alloc.clone());
+		let s1 = r.insert("foo");
+		assert_eq!(live.load(std::sync::atomic::Ordering::SeqCst), 1);
+		let s2 = r.insert("foo");
+		assert!(s1.ptr_eq(&s2));
+		assert_eq!(live.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+		drop(s1);
+		drop(s2);
+		assert_eq!(live.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+		let standalone = Symbol::new_in("bar", alloc.clone());
+		assert_eq!(live.load(std::sync::atomic::Ordering::SeqCst), 1);
+		let weak = standalone.downgrade();
+		drop(standalone);
+		// a `WeakSymbol` keeps the allocation (but not the string) alive
+		assert_eq!(live.load(std::sync::atomic::Ordering::SeqCst), 1);
+		assert!(weak.upgrade().is_none());
+		drop(weak);
+		assert_eq!(live.load(std::sync::atomic::Ordering::SeqCst), 0);
+	}
+
+	#[test]
+	fn persistent() {
+		let r = SymbolRegistry::new();
+		let s1 = r.insert_persistent("keyword");
+		let s2 = r.insert("keyword");
+		assert!(s1.ptr_eq(&s2));
+		drop(s1);
+		drop(s2);
+		// still registered: persistent symbols are never removed
+		assert!(r.find("keyword").is_some());
+	}
+
+	#[test]
+	fn introspection() {
+		let r = SymbolRegistry::new();
+		assert_eq!(r.len(), 0);
+		assert!(r.is_empty());
+
+		let foo = r.insert("foo");
+		let _bar = r.insert("bar");
+		assert_eq!(r.len(), 2);
+		assert!(!r.is_empty());
+
+		let mut seen: Vec<String> = Vec::new();
+		r.for_each(|s| seen.push(s.value().to_owned()));
+		seen.sort();
+		assert_eq!(seen, vec!["bar".to_owned(), "foo".to_owned()]);
+
+		let mut snapshot: Vec<String> = r.snapshot().iter().map(|s| s.value().to_owned()).collect();
+		snapshot.sort();
+		assert_eq!(snapshot, vec!["bar".to_owned(), "foo".to_owned()]);
+
+		r.retain(|s| s.value() == "foo");
+		assert_eq!(r.len(), 1);
+		assert!(r.find("foo").is_some());
+		assert!(r.find("bar").is_none());
+		// the retained symbol's handle is unaffected by retain
+		assert_eq!(&*foo, "foo");
+
+		r.clear();
+		assert!(r.is_empty());
+		assert!(r.find("foo").is_none());
+	}
+
+	#[test]
+	fn for_each_and_retain_can_reenter_registry() {
+		// f()/predicate must run after the registry lock was released,
+		// or calling back into the registry here would deadlock
+		let r = SymbolRegistry::new();
+		let _foo = r.insert("foo");
+		let _bar = r.insert("bar");
+
+		r.for_each(|s| {
+			let _ = r.find(s.value());
+		});
+
+		let mut kept_alive = Vec::new();
+		r.retain(|s| {
+			kept_alive.push(r.insert("inserted while retaining"));
+			s.value() != "bar"
+		});
+		assert!(r.find("foo").is_some());
+		assert!(r.find("bar").is_none());
+		assert!(r.find("inserted while retaining").is_some());
+	}
+
+	#[test]
+	fn clear_then_reinsert_keeps_new_entry() {
+		let r = SymbolRegistry::new();
+		let first = r.insert("foo");
+		r.clear();
+		let second = r.insert("foo");
+		assert!(!first.ptr_eq(&second));
+
+		// dropping the untracked `first` must not evict `second`'s
+		// still-current registration
+		drop(first);
+		assert!(r.find("foo").is_some());
+		assert!(r.find("foo").unwrap().ptr_eq(&second));
+	}
 }
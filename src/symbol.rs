@@ -1,5 +1,5 @@
-use crate::{Registry, SymbolNoRc, SymbolRegistry};
-use std::alloc;
+use crate::alloc::{Allocator, Global};
+use crate::{ById, Registry, SymbolNoRc, SymbolRegistry};
 use std::cell::UnsafeCell;
 use std::fmt;
 use std::marker::PhantomData;
@@ -8,56 +8,105 @@ use std::ptr::NonNull;
 use std::sync::{Mutex, Weak};
 use std::sync::atomic::{fence, AtomicUsize, Ordering};
 
-fn make_layout(len: usize) -> alloc::Layout {
-	alloc::Layout::from_size_align(
-		size_of::<Inner>().checked_add(len as usize).expect("size overflow"),
-		align_of::<Inner>(),
+fn make_layout<A: Allocator>(len: usize) -> std::alloc::Layout {
+	std::alloc::Layout::from_size_align(
+		size_of::<Inner<A>>().checked_add(len as usize).expect("size overflow"),
+		align_of::<Inner<A>>(),
 	).expect("size overflow")
 }
 
 const MAX_REFCOUNT: usize = isize::max_value() as usize;
 
-struct Inner {
+struct Inner<A: Allocator> {
 	strong: AtomicUsize,
-	registry: UnsafeCell<Option<Weak<Mutex<Registry>>>>,
+	// all strong handles collectively hold a single weak reference (as
+	// `Arc` does), released once the last strong handle drops
+	weak: AtomicUsize,
+	registry: UnsafeCell<Option<Weak<Mutex<Registry<A>>>>>,
 	len: usize,
+	// persistent symbols are never removed from their registry by
+	// `Drop` and never deallocated; `Drop` is a no-op for them.
+	persistent: bool,
+	// wrapped in `ManuallyDrop` so `drop_in_place`-ing the rest of
+	// `Inner` (done before we're done needing the allocator to free the
+	// very block it lives in) doesn't drop it out from under us
+	alloc: ManuallyDrop<A>,
 }
 
-const DATA_OFFSET: usize = size_of::<Inner>();
+fn data_offset<A: Allocator>() -> usize {
+	size_of::<Inner<A>>()
+}
 
 /// Stores a shared string
 ///
 /// Sharing established by either cloning the `Symbol` or by looking it
 /// up in the registry.
-pub struct Symbol {
-	ptr: NonNull<Inner>,
-	_phantom: PhantomData<Inner>,
+///
+/// Generic over the allocator `A` (defaulting to [`Global`]) backing
+/// the header+string allocation; see [`Symbol::new_in`].
+pub struct Symbol<A: Allocator = Global> {
+	ptr: NonNull<Inner<A>>,
+	_phantom: PhantomData<Inner<A>>,
 }
 
-unsafe impl Send for Symbol {}
-unsafe impl Sync for Symbol {}
+unsafe impl<A: Allocator + Send> Send for Symbol<A> {}
+unsafe impl<A: Allocator + Sync> Sync for Symbol<A> {}
 
-impl Symbol {
+impl Symbol<Global> {
 	/// Create new standalone symbol
 	pub fn new(data: &str) -> Self {
+		Self::new_in(data, Global)
+	}
+
+	/// Intern a string in the process-wide global registry
+	///
+	/// See [`SymbolRegistry::global`].
+	pub fn intern(value: &str) -> Symbol<Global> {
+		SymbolRegistry::global().insert(value)
+	}
+
+	/// Intern a string in the process-wide global registry as a
+	/// persistent symbol
+	///
+	/// See [`SymbolRegistry::insert_persistent`].
+	pub fn intern_static(value: &str) -> Symbol<Global> {
+		SymbolRegistry::global().insert_persistent(value)
+	}
+}
+
+impl<A: Allocator> Symbol<A> {
+	/// Create new standalone symbol, allocated through `alloc`
+	pub fn new_in(data: &str, alloc: A) -> Self {
+		Self::new_raw(data, false, alloc)
+	}
+
+	pub(crate) fn new_persistent_in(data: &str, alloc: A) -> Self {
+		Self::new_raw(data, true, alloc)
+	}
+
+	fn new_raw(data: &str, persistent: bool, alloc: A) -> Self {
 		let len = data.len();
-		let inner = Inner {
-			strong: AtomicUsize::new(1),
-			registry: UnsafeCell::new(None),
-			len,
-		};
 		unsafe {
-			let ptr = alloc::alloc(make_layout(len));
+			let ptr = alloc.alloc(make_layout::<A>(len));
 			assert_ne!(ptr, std::ptr::null_mut(), "allocation failed");
-			(ptr as *mut Inner).write(inner);
+
+			let inner = Inner {
+				strong: AtomicUsize::new(1),
+				weak: AtomicUsize::new(1),
+				registry: UnsafeCell::new(None),
+				len,
+				persistent,
+				alloc: ManuallyDrop::new(alloc),
+			};
+			(ptr as *mut Inner<A>).write(inner);
 
 			let buf = {
-				let data: *mut u8 = ptr.add(DATA_OFFSET);
+				let data: *mut u8 = ptr.add(data_offset::<A>());
 				std::slice::from_raw_parts_mut(data, len)
 			};
 			buf.copy_from_slice(data.as_bytes());
 			Symbol {
-				ptr: NonNull::new_unchecked(ptr as *mut Inner), // checked above
+				ptr: NonNull::new_unchecked(ptr as *mut Inner<A>), // checked above
 				_phantom: PhantomData,
 			}
 		}
@@ -67,7 +116,7 @@ impl Symbol {
 	pub fn value(&self) -> &str {
 		let len = self.inner().len as usize;
 		unsafe {
-			let data: *const u8 = (self.ptr.as_ptr() as *const u8).add(DATA_OFFSET);
+			let data: *const u8 = (self.ptr.as_ptr() as *const u8).add(data_offset::<A>());
 			std::str::from_utf8_unchecked(
 				std::slice::from_raw_parts(data, len)
 			)
@@ -82,26 +131,52 @@ impl Symbol {
 		self.ptr == other.ptr
 	}
 
-	pub(crate) unsafe fn set_registry(&self, registry: Weak<Mutex<Registry>>) {
+	pub(crate) fn addr(&self) -> usize {
+		self.ptr.as_ptr() as usize
+	}
+
+	/// View this symbol as [`ById`], comparing/hashing/ordering it by
+	/// identity instead of by value
+	pub fn by_id(&self) -> ById<A> {
+		ById(self.clone())
+	}
+
+	/// Create a [`WeakSymbol`] pointing at the same string
+	///
+	/// The string keeps living as long as there is at least one
+	/// `Symbol` around, regardless of how many `WeakSymbol`s point at
+	/// it.
+	pub fn downgrade(&self) -> WeakSymbol<A> {
+		let old_size = self.inner().weak.fetch_add(1, Ordering::Relaxed);
+
+		if old_size > MAX_REFCOUNT {
+			std::process::abort();
+		}
+
+		WeakSymbol { ptr: self.ptr }
+	}
+
+	pub(crate) unsafe fn set_registry(&self, registry: Weak<Mutex<Registry<A>>>) {
 		*self.inner().registry.get() = Some(registry);
 	}
 
-	pub(crate) fn clone_no_rc(&self) -> SymbolNoRc {
+	pub(crate) fn clone_no_rc(&self) -> SymbolNoRc<A> {
 		SymbolNoRc(ManuallyDrop::new(Symbol {
 			ptr: self.ptr,
 			_phantom: self._phantom,
 		}))
 	}
 
-	fn inner(&self) -> &Inner {
+	fn inner(&self) -> &Inner<A> {
 		unsafe { self.ptr.as_ref() }
 	}
 
-	pub(crate) fn registry(&self) -> Option<SymbolRegistry> {
+	pub(crate) fn registry(&self) -> Option<SymbolRegistry<A>> {
 		let reg = unsafe { &*self.inner().registry.get() };
 		reg.as_ref().and_then(|r| {
 			Some(SymbolRegistry {
 				registry: Weak::upgrade(r)?,
+				alloc: (*self.inner().alloc).clone(),
 			})
 		})
 	}
@@ -117,25 +192,26 @@ impl Symbol {
 				return;
 			}
 
-			// now we got the registry lock *and* rc is 0. remove from registry.
-
-			#[cfg(debug_assertions)]
-			{
-				let have = reg.content.get(&**self).expect("must be registered");
-				assert!(have.0.ptr_eq(self), "must match expected entry");
+			// now we got the registry lock *and* rc is 0. remove from
+			// registry, but only if it's still tracking *this*
+			// allocation: `retain`/`clear` can drop the registry's
+			// tracking of a symbol while `Symbol` handles to it are
+			// still alive, and a new symbol for the same value may
+			// have been inserted since.
+			if reg.content.get(&**self).is_some_and(|have| have.0.ptr_eq(self)) {
+				reg.content.remove(&**self);
 			}
-			// (could to debug lookup to make sure entry is actually)
-
-			reg.content.remove(&**self);
 		}
 
-		let layout = make_layout(self.inner().len);
 		std::ptr::drop_in_place(self.ptr.as_ptr());
-		alloc::dealloc(self.ptr.as_ptr() as *mut u8, layout);
+
+		// release the weak reference all strong handles collectively
+		// held; only actually deallocate once no `WeakSymbol` is left
+		drop(WeakSymbol { ptr: self.ptr });
 	}
 }
 
-impl std::ops::Deref for Symbol {
+impl<A: Allocator> std::ops::Deref for Symbol<A> {
 	type Target = str;
 
 	fn deref(&self) -> &Self::Target {
@@ -143,13 +219,13 @@ impl std::ops::Deref for Symbol {
 	}
 }
 
-impl std::borrow::Borrow<str> for Symbol {
+impl<A: Allocator> std::borrow::Borrow<str> for Symbol<A> {
 	fn borrow(&self) -> &str {
 		self.value()
 	}
 }
 
-impl Clone for Symbol {
+impl<A: Allocator> Clone for Symbol<A> {
 	fn clone(&self) -> Self {
 		let old_size = self.inner().strong.fetch_add(1, Ordering::Relaxed);
 
@@ -164,9 +240,14 @@ impl Clone for Symbol {
 	}
 }
 
-impl Drop for Symbol {
+impl<A: Allocator> Drop for Symbol<A> {
 	#[inline]
 	fn drop(&mut self) {
+		if self.inner().persistent {
+			// never removed from the registry, never deallocated: no
+			// need to touch the refcount at all
+			return;
+		}
 		if self.inner().strong.fetch_sub(1, Ordering::Release) != 1 {
 			return;
 		}
@@ -177,28 +258,28 @@ impl Drop for Symbol {
 	}
 }
 
-impl PartialEq for Symbol {
-	fn eq(&self, other: &Symbol) -> bool {
+impl<A: Allocator> PartialEq for Symbol<A> {
+	fn eq(&self, other: &Symbol<A>) -> bool {
 		self.value() == other.value()
 	}
 }
 
-impl Eq for Symbol {
+impl<A: Allocator> Eq for Symbol<A> {
 }
 
-impl PartialOrd for Symbol {
-	fn partial_cmp(&self, other: &Symbol) -> Option<std::cmp::Ordering> {
+impl<A: Allocator> PartialOrd for Symbol<A> {
+	fn partial_cmp(&self, other: &Symbol<A>) -> Option<std::cmp::Ordering> {
 		Some(self.value().cmp(other.value()))
 	}
 }
 
-impl Ord for Symbol {
-	fn cmp(&self, other: &Symbol) -> std::cmp::Ordering {
+impl<A: Allocator> Ord for Symbol<A> {
+	fn cmp(&self, other: &Symbol<A>) -> std::cmp::Ordering {
 		self.value().cmp(other.value())
 	}
 }
 
-impl std::hash::Hash for Symbol {
+impl<A: Allocator> std::hash::Hash for Symbol<A> {
 	fn hash<H>(&self, state: &mut H)
 	where
 		H: std::hash::Hasher,
@@ -207,14 +288,91 @@ impl std::hash::Hash for Symbol {
 	}
 }
 
-impl fmt::Debug for Symbol {
+impl<A: Allocator> fmt::Debug for Symbol<A> {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		(**self).fmt(f)
 	}
 }
 
-impl From<&str> for Symbol {
+impl From<&str> for Symbol<Global> {
 	fn from(v: &str) -> Self {
 		Symbol::new(v)
 	}
 }
+
+/// A weak reference to a [`Symbol`]
+///
+/// Doesn't keep the string data alive; obtain through
+/// [`Symbol::downgrade`], and get back a `Symbol` (if the string is
+/// still alive) through [`WeakSymbol::upgrade`].
+pub struct WeakSymbol<A: Allocator = Global> {
+	ptr: NonNull<Inner<A>>,
+}
+
+unsafe impl<A: Allocator + Send> Send for WeakSymbol<A> {}
+unsafe impl<A: Allocator + Sync> Sync for WeakSymbol<A> {}
+
+impl<A: Allocator> WeakSymbol<A> {
+	fn inner(&self) -> &Inner<A> {
+		unsafe { self.ptr.as_ref() }
+	}
+
+	/// Try to get a `Symbol` back, if the string is still alive
+	pub fn upgrade(&self) -> Option<Symbol<A>> {
+		let mut cur = self.inner().strong.load(Ordering::Relaxed);
+		loop {
+			if cur == 0 {
+				// string already gone (or being dropped right now)
+				return None;
+			}
+			if cur > MAX_REFCOUNT {
+				std::process::abort();
+			}
+			match self.inner().strong.compare_exchange_weak(cur, cur + 1, Ordering::Acquire, Ordering::Relaxed) {
+				Ok(_) => {
+					return Some(Symbol {
+						ptr: self.ptr,
+						_phantom: PhantomData,
+					});
+				},
+				Err(observed) => cur = observed,
+			}
+		}
+	}
+}
+
+impl<A: Allocator> Clone for WeakSymbol<A> {
+	fn clone(&self) -> Self {
+		let old_size = self.inner().weak.fetch_add(1, Ordering::Relaxed);
+
+		if old_size > MAX_REFCOUNT {
+			std::process::abort();
+		}
+
+		WeakSymbol { ptr: self.ptr }
+	}
+}
+
+impl<A: Allocator> Drop for WeakSymbol<A> {
+	fn drop(&mut self) {
+		if self.inner().weak.fetch_sub(1, Ordering::Release) != 1 {
+			return;
+		}
+		fence(Ordering::Acquire);
+		unsafe {
+			let layout = make_layout::<A>(self.inner().len);
+			// `Inner::alloc` is wrapped in `ManuallyDrop`, so it's still
+			// intact here even if `Symbol::drop_slow` already ran
+			// `drop_in_place` on the rest of `Inner`; take ownership of
+			// it to free the block through the allocator it came from
+			let alloc = ManuallyDrop::into_inner(std::ptr::read(&self.inner().alloc));
+			alloc.dealloc(self.ptr.as_ptr() as *mut u8, layout);
+		}
+	}
+}
+
+impl<A: Allocator> fmt::Debug for WeakSymbol<A> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("(WeakSymbol)")
+	}
+}
@@ -1,31 +1,32 @@
+use crate::alloc::{Allocator, Global};
 use crate::Symbol;
 use std::fmt;
 use std::mem::ManuallyDrop;
 
-pub(crate) struct SymbolNoRc(pub(crate) ManuallyDrop<Symbol>);
+pub(crate) struct SymbolNoRc<A: Allocator = Global>(pub(crate) ManuallyDrop<Symbol<A>>);
 
-impl SymbolNoRc {
-	pub(crate) fn symbol(&self) -> Symbol {
+impl<A: Allocator> SymbolNoRc<A> {
+	pub(crate) fn symbol(&self) -> Symbol<A> {
 		(*self.0).clone()
 	}
 }
 
-impl std::borrow::Borrow<str> for SymbolNoRc {
+impl<A: Allocator> std::borrow::Borrow<str> for SymbolNoRc<A> {
 	fn borrow(&self) -> &str {
 		self.0.value()
 	}
 }
 
-impl PartialEq for SymbolNoRc {
-	fn eq(&self, other: &SymbolNoRc) -> bool {
+impl<A: Allocator> PartialEq for SymbolNoRc<A> {
+	fn eq(&self, other: &SymbolNoRc<A>) -> bool {
 		self.0.value() == other.0.value()
 	}
 }
 
-impl Eq for SymbolNoRc {
+impl<A: Allocator> Eq for SymbolNoRc<A> {
 }
 
-impl std::hash::Hash for SymbolNoRc {
+impl<A: Allocator> std::hash::Hash for SymbolNoRc<A> {
 	fn hash<H>(&self, state: &mut H)
 	where
 		H: std::hash::Hasher,
@@ -34,7 +35,7 @@ impl std::hash::Hash for SymbolNoRc {
 	}
 }
 
-impl fmt::Debug for SymbolNoRc {
+impl<A: Allocator> fmt::Debug for SymbolNoRc<A> {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		(**self.0).fmt(f)
 	}